@@ -142,6 +142,16 @@ pub extern "C" fn zeroidc_is_running(ptr: *mut ZeroIDC) -> bool {
     idc.is_running()
 }
 
+#[no_mangle]
+pub extern "C" fn zeroidc_auth_required(ptr: *mut ZeroIDC) -> bool {
+    let idc = unsafe {
+        assert!(!ptr.is_null());
+        &mut *ptr
+    };
+
+    idc.auth_required()
+}
+
 #[no_mangle]
 pub extern "C" fn zeroidc_get_exp_time(ptr: *mut ZeroIDC) -> u64 {
     let id = unsafe {
@@ -264,6 +274,105 @@ pub extern "C" fn zeroidc_token_exchange(idc: *mut ZeroIDC, code: *const c_char)
     }
 }
 
+#[cfg(any(
+    all(target_os = "linux", target_arch = "x86"),
+    all(target_os = "linux", target_arch = "x86_64"),
+    all(target_os = "linux", target_arch = "aarch64"),
+    target_os = "windows",
+    target_os = "macos",
+))]
+#[no_mangle]
+pub extern "C" fn zeroidc_begin_device_flow(idc: *mut ZeroIDC) -> bool {
+    if idc.is_null() {
+        println!("idc is null");
+        return false;
+    }
+    let idc = unsafe { &mut *idc };
+
+    match idc.begin_device_flow() {
+        Ok(()) => true,
+        Err(e) => {
+            println!("begin_device_flow err: {}", e);
+            false
+        }
+    }
+}
+
+#[cfg(any(
+    all(target_os = "linux", target_arch = "x86"),
+    all(target_os = "linux", target_arch = "x86_64"),
+    all(target_os = "linux", target_arch = "aarch64"),
+    target_os = "windows",
+    target_os = "macos",
+))]
+#[no_mangle]
+pub extern "C" fn zeroidc_get_device_user_code(idc: *mut ZeroIDC) -> *mut c_char {
+    if idc.is_null() {
+        println!("idc is null");
+        return std::ptr::null_mut();
+    }
+    let idc = unsafe { &mut *idc };
+
+    let s = CString::new(idc.device_user_code()).unwrap();
+    s.into_raw()
+}
+
+#[cfg(any(
+    all(target_os = "linux", target_arch = "x86"),
+    all(target_os = "linux", target_arch = "x86_64"),
+    all(target_os = "linux", target_arch = "aarch64"),
+    target_os = "windows",
+    target_os = "macos",
+))]
+#[no_mangle]
+pub extern "C" fn zeroidc_get_device_verification_url(idc: *mut ZeroIDC) -> *mut c_char {
+    if idc.is_null() {
+        println!("idc is null");
+        return std::ptr::null_mut();
+    }
+    let idc = unsafe { &mut *idc };
+
+    let s = CString::new(idc.device_verification_url()).unwrap();
+    s.into_raw()
+}
+
+#[cfg(any(
+    all(target_os = "linux", target_arch = "x86"),
+    all(target_os = "linux", target_arch = "x86_64"),
+    all(target_os = "linux", target_arch = "aarch64"),
+    target_os = "windows",
+    target_os = "macos",
+))]
+#[no_mangle]
+pub extern "C" fn zeroidc_device_token_exchange(idc: *mut ZeroIDC) -> *mut c_char {
+    if idc.is_null() {
+        println!("idc is null");
+        return std::ptr::null_mut();
+    }
+    let idc = unsafe { &mut *idc };
+
+    let ret = idc.do_device_token_exchange();
+    match ret {
+        Ok(ret) => {
+            #[cfg(debug_assertions)]
+            {
+                println!("do_device_token_exchange ret: {}", ret);
+            }
+            let ret = CString::new(ret).unwrap();
+            ret.into_raw()
+        }
+        Err(e) => {
+            #[cfg(debug_assertions)]
+            {
+                println!("do_device_token_exchange err: {}", e);
+            }
+            let errstr = format!("{{\"errorMessage\": \"{}\"}}", e);
+            let ret = CString::new(errstr).unwrap();
+            ret.into_raw()
+        }
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn zeroidc_get_url_param_value(param: *const c_char, path: *const c_char) -> *mut c_char {
     if param.is_null() {
@@ -23,12 +23,19 @@ use crate::error::*;
 
 use bytes::Bytes;
 use jwt::Token;
-use openidconnect::core::{CoreClient, CoreProviderMetadata, CoreResponseType};
+use openidconnect::core::{
+    CoreAuthDisplay, CoreClaimName, CoreClaimType, CoreClient, CoreClientAuthMethod, CoreDeviceAuthorizationResponse,
+    CoreGrantType, CoreJsonWebKey, CoreJsonWebKeyType, CoreJsonWebKeyUse, CoreJweContentEncryptionAlgorithm,
+    CoreJweKeyManagementAlgorithm, CoreJwsSigningAlgorithm, CoreResponseMode, CoreResponseType,
+    CoreSubjectIdentifierType,
+};
 use openidconnect::reqwest::http_client;
 use openidconnect::{
-    AccessToken, AccessTokenHash, AuthenticationFlow, AuthorizationCode, ClientId, CsrfToken, IssuerUrl, Nonce,
-    OAuth2TokenResponse, PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, RefreshToken, Scope, TokenResponse,
+    AccessToken, AccessTokenHash, AdditionalProviderMetadata, AuthenticationFlow, AuthorizationCode, ClientId,
+    CsrfToken, DeviceAuthorizationUrl, IssuerUrl, Nonce, OAuth2TokenResponse, PkceCodeChallenge, PkceCodeVerifier,
+    ProviderMetadata, RedirectUrl, RefreshToken, Scope, TokenResponse,
 };
+use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::str::from_utf8;
 use std::sync::{Arc, Mutex};
@@ -67,11 +74,13 @@ struct Inner {
     refresh_token: Option<RefreshToken>,
     exp_time: u64,
     kick: bool,
+    auth_required: bool,
 
     url: Option<Url>,
     csrf_token: Option<CsrfToken>,
     nonce: Option<Nonce>,
     pkce_verifier: Option<PkceCodeVerifier>,
+    device_auth: Option<CoreDeviceAuthorizationResponse>,
 }
 
 impl Inner {
@@ -89,6 +98,33 @@ fn nonce_func(nonce: String) -> Box<dyn Fn() -> Nonce> {
     Box::new(move || Nonce::new(nonce.to_string()))
 }
 
+// The device authorization endpoint is an extension to the standard provider
+// metadata, so discovery has to be taught about it
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct DeviceEndpointProviderMetadata {
+    device_authorization_endpoint: Option<DeviceAuthorizationUrl>,
+}
+
+impl AdditionalProviderMetadata for DeviceEndpointProviderMetadata {}
+
+type DeviceProviderMetadata = ProviderMetadata<
+    DeviceEndpointProviderMetadata,
+    CoreAuthDisplay,
+    CoreClientAuthMethod,
+    CoreClaimName,
+    CoreClaimType,
+    CoreGrantType,
+    CoreJweContentEncryptionAlgorithm,
+    CoreJweKeyManagementAlgorithm,
+    CoreJwsSigningAlgorithm,
+    CoreJsonWebKeyType,
+    CoreJsonWebKeyUse,
+    CoreJsonWebKey,
+    CoreResponseMode,
+    CoreResponseType,
+    CoreSubjectIdentifierType,
+>;
+
 #[cfg(debug_assertions)]
 fn systemtime_strftime<T>(dt: T, format: &str) -> String
 where
@@ -130,11 +166,13 @@ impl ZeroIDC {
                 refresh_token: None,
                 exp_time: 0,
                 kick: false,
+                auth_required: false,
 
                 url: None,
                 csrf_token: None,
                 nonce: None,
                 pkce_verifier: None,
+                device_auth: None,
             })),
         };
 
@@ -144,18 +182,25 @@ impl ZeroIDC {
         );
         let iss = IssuerUrl::new(issuer.to_string())?;
 
-        let provider_meta = CoreProviderMetadata::discover(&iss, http_client)?;
+        let provider_meta = DeviceProviderMetadata::discover(&iss, http_client)?;
+        let device_authorization_endpoint = provider_meta
+            .additional_metadata()
+            .device_authorization_endpoint
+            .clone();
 
         let r = format!("http://localhost:{}/sso", local_web_port);
         let redir_url = Url::parse(&r)?;
 
         let redirect = RedirectUrl::new(redir_url.to_string())?;
 
-        idc.inner.lock().unwrap().oidc_client = Some(
-            CoreClient::from_provider_metadata(provider_meta, ClientId::new(client_id.to_string()), None)
-                .set_redirect_uri(redirect)
-                .set_auth_type(openidconnect::AuthType::RequestBody),
-        );
+        let mut client = CoreClient::from_provider_metadata(provider_meta, ClientId::new(client_id.to_string()), None)
+            .set_redirect_uri(redirect)
+            .set_auth_type(openidconnect::AuthType::RequestBody);
+        if let Some(device_authorization_endpoint) = device_authorization_endpoint {
+            client = client.set_device_authorization_uri(device_authorization_endpoint);
+        }
+
+        idc.inner.lock().unwrap().oidc_client = Some(client);
 
         Ok(idc)
     }
@@ -340,6 +385,12 @@ impl ZeroIDC {
                 }
                 // end run loop
 
+                // The loop only exits with a cleared expiry when token refresh
+                // failed permanently, meaning the user has to log in again
+                if inner_local.lock().unwrap().exp_time == 0 {
+                    inner_local.lock().unwrap().auth_required = true;
+                }
+
                 println!("thread done!");
                 inner_local.lock().unwrap().running = false;
                 println!("set idc thread running flag to false");
@@ -365,6 +416,10 @@ impl ZeroIDC {
         return self.inner.lock().unwrap().exp_time;
     }
 
+    pub fn auth_required(&mut self) -> bool {
+        return self.inner.lock().unwrap().auth_required;
+    }
+
     pub fn set_nonce_and_csrf(&mut self, csrf_token: String, nonce: String) {
         let local = Arc::clone(&self.inner);
         (*local.lock().expect("can't lock inner")).as_opt().map(|i| {
@@ -463,6 +518,209 @@ impl ZeroIDC {
         }
     }
 
+    pub fn begin_device_flow(&mut self) -> Result<(), SSOExchangeError> {
+        let local = Arc::clone(&self.inner);
+
+        let client = match local.lock().unwrap().oidc_client.clone() {
+            Some(c) => c,
+            None => return Err(SSOExchangeError::new("no oidc client".to_string())),
+        };
+
+        let req = match client.exchange_device_code() {
+            Ok(req) => req,
+            Err(e) => {
+                println!("no device authorization endpoint: {}", e);
+                return Err(SSOExchangeError::new(
+                    "provider does not support the device authorization flow".to_string(),
+                ));
+            }
+        };
+
+        let res: Result<CoreDeviceAuthorizationResponse, _> = req
+            .add_scope(Scope::new("profile".to_string()))
+            .add_scope(Scope::new("email".to_string()))
+            .add_scope(Scope::new("offline_access".to_string()))
+            .request(http_client);
+
+        match res {
+            Ok(details) => {
+                local.lock().unwrap().device_auth = Some(details);
+                Ok(())
+            }
+            Err(e) => {
+                println!("device authorization request error: {}", e);
+                Err(SSOExchangeError::new("error requesting device authorization".to_string()))
+            }
+        }
+    }
+
+    pub fn device_user_code(&self) -> String {
+        let code = (*self.inner.lock().expect("can't lock inner"))
+            .as_opt()
+            .map(|i| match i.device_auth.as_ref() {
+                Some(d) => d.user_code().secret().to_string(),
+                None => "".to_string(),
+            });
+
+        match code {
+            Some(code) => code,
+            None => "".to_string(),
+        }
+    }
+
+    pub fn device_verification_url(&self) -> String {
+        let url = (*self.inner.lock().expect("can't lock inner"))
+            .as_opt()
+            .map(|i| match i.device_auth.as_ref() {
+                // the complete URI embeds the user code, so prefer it when the
+                // provider supplies one
+                Some(d) => match d.verification_uri_complete() {
+                    Some(u) => u.secret().to_string(),
+                    None => d.verification_uri().to_string(),
+                },
+                None => "".to_string(),
+            });
+
+        match url {
+            Some(url) => url,
+            None => "".to_string(),
+        }
+    }
+
+    pub fn do_device_token_exchange(&mut self) -> Result<String, SSOExchangeError> {
+        let local = Arc::clone(&self.inner);
+
+        let (client, details, auth_endpoint, csrf) = {
+            let inner = local.lock().unwrap();
+            // leave device_auth in place while the poll below runs so the
+            // user code and verification URL stay visible to the service
+            let details = match inner.device_auth.clone() {
+                Some(d) => d,
+                None => return Err(SSOExchangeError::new("no device authorization in progress".to_string())),
+            };
+            let client = match inner.oidc_client.clone() {
+                Some(c) => c,
+                None => return Err(SSOExchangeError::new("no oidc client".to_string())),
+            };
+            let csrf = match inner.csrf_token.clone() {
+                Some(t) => t.secret().to_owned(),
+                None => "".to_string(),
+            };
+            (client, details, inner.auth_endpoint.clone(), csrf)
+        };
+
+        // this blocks until the user approves the device at the verification
+        // URL, the code expires, or the provider returns a hard error, so the
+        // inner lock must not be held across it
+        let tok = client.exchange_device_access_token(&details).request(http_client, sleep, None);
+
+        // the code has now been consumed (approved, denied, or expired), so a
+        // new flow has to be started for another attempt
+        local.lock().unwrap().device_auth = None;
+
+        let tok = match tok {
+            Ok(tok) => tok,
+            Err(e) => {
+                println!("device token exchange error: {}", e);
+                return Err(SSOExchangeError::new("error exchanging device code for token".to_string()));
+            }
+        };
+
+        let id_token = match tok.id_token() {
+            Some(t) => t,
+            None => {
+                println!("no id token");
+                return Err(SSOExchangeError::new("no id token in response".to_string()));
+            }
+        };
+
+        // the device flow carries no nonce, so only the signature and
+        // standard claims can be checked here
+        if let Err(e) = id_token.claims(&client.id_token_verifier(), |_: Option<&Nonce>| Ok(())) {
+            println!("claim verification error: {}", e);
+            return Err(SSOExchangeError::new("error verifying claims".to_string()));
+        }
+
+        let split = csrf.split('_').collect::<Vec<&str>>();
+        if split.len() != 2 {
+            return Err(SSOExchangeError::new("error splitting state token".to_string()));
+        }
+
+        let params = [("id_token", id_token.to_string()), ("state", split[0].to_string())];
+        let http = reqwest::blocking::Client::new();
+        let res = http.post(auth_endpoint).form(&params).send();
+
+        match res {
+            Ok(res) => {
+                if res.status() == 200 {
+                    #[cfg(debug_assertions)]
+                    {
+                        println!("hit url: {}", res.url().as_str());
+                        println!("Status: {}", res.status());
+                    }
+
+                    let idt = &id_token.to_string();
+
+                    let t: Result<Token<jwt::Header, jwt::Claims, jwt::Unverified<'_>>, jwt::Error> =
+                        Token::parse_unverified(idt);
+
+                    let exp = match t {
+                        Ok(t) => match t.claims().registered.expiration {
+                            Some(exp) => exp,
+                            None => {
+                                println!("no expiration in id token");
+                                return Err(SSOExchangeError::new("no expiration in id token".to_string()));
+                            }
+                        },
+                        Err(e) => {
+                            println!("error parsing id token: {}", e);
+                            return Err(SSOExchangeError::new("error parsing id token".to_string()));
+                        }
+                    };
+
+                    let mut should_start = false;
+                    {
+                        let mut inner = local.lock().unwrap();
+                        inner.exp_time = exp;
+                        println!("Set exp time to: {:?}", inner.exp_time);
+                        inner.access_token = Some(tok.access_token().clone());
+                        inner.auth_required = false;
+                        if let Some(t) = tok.refresh_token() {
+                            inner.refresh_token = Some(t.clone());
+                            should_start = true;
+                        }
+                    }
+                    if should_start {
+                        self.start();
+                    }
+
+                    let bytes = match res.bytes() {
+                        Ok(bytes) => bytes,
+                        Err(_) => Bytes::from(""),
+                    };
+
+                    let bytes = match from_utf8(bytes.as_ref()) {
+                        Ok(bytes) => bytes.to_string(),
+                        Err(_) => "".to_string(),
+                    };
+
+                    Ok(bytes)
+                } else if res.status() == 402 {
+                    Err(SSOExchangeError::new(
+                        "additional license seats required. Please contact your network administrator.".to_string(),
+                    ))
+                } else {
+                    Err(SSOExchangeError::new("error from central endpoint".to_string()))
+                }
+            }
+            Err(res) => {
+                println!("error result: {}", res);
+                println!("Post error: {}", res);
+                Err(SSOExchangeError::new("error from central endpoint".to_string()))
+            }
+        }
+    }
+
     pub fn do_token_exchange(&mut self, code: &str) -> Result<String, SSOExchangeError> {
         let local = Arc::clone(&self.inner);
         let mut should_start = false;
@@ -591,6 +849,7 @@ impl ZeroIDC {
                                     }
 
                                     i.access_token = Some(tok.access_token().clone());
+                                    i.auth_required = false;
                                     if let Some(t) = tok.refresh_token() {
                                         i.refresh_token = Some(t.clone());
                                         should_start = true;
@@ -24,6 +24,7 @@
 #include <thread>
 #include <mutex>
 #include <condition_variable>
+#include <atomic>
 
 #ifdef __FreeBSD__
 #include <sched.h>
@@ -282,6 +283,17 @@ std::string http_log(const httplib::Request &req, const httplib::Response &res)
 class NetworkState
 {
 public:
+#if ZT_SSO_ENABLED
+	// Shared with the detached device-flow exchange thread so the idc
+	// instance can outlive this network if the user never approves the code.
+	// Both the thread and the network teardown exchange finished to true;
+	// whichever arrives second frees the idc instance.
+	struct DeviceFlowState {
+		DeviceFlowState() : finished(false) {}
+		std::atomic<bool> finished;
+	};
+#endif
+
 	NetworkState()
 		: _webPort(9993)
 		, _tap((EthernetTap *)0)
@@ -305,7 +317,16 @@ public:
 #if ZT_SSO_ENABLED
 		if (_idc) {
 			zeroidc::zeroidc_stop(_idc);
-			zeroidc::zeroidc_delete(_idc);
+			if (_deviceFlow) {
+				if (_deviceFlow->finished.exchange(true)) {
+					// the exchange thread is already done with the idc instance
+					zeroidc::zeroidc_delete(_idc);
+				}
+				// otherwise the thread is still blocked polling the provider
+				// and frees the instance itself when the poll returns
+			} else {
+				zeroidc::zeroidc_delete(_idc);
+			}
 			_idc = nullptr;
 		}
 #endif
@@ -467,6 +488,75 @@ public:
 #endif
 	}
 
+	bool startDeviceAuthFlow() {
+#if ZT_SSO_ENABLED
+		if (_idc == nullptr) {
+			fprintf(stderr, "idc is null\n");
+			return false;
+		}
+
+		if (_deviceFlow && !_deviceFlow->finished.load()) {
+			// already polling the provider
+			return true;
+		}
+
+		// Both steps talk to the IdP: the begin request can hang for the
+		// full connect timeout when egress is restricted and the token
+		// exchange blocks until the user approves the device at the
+		// verification URL (or the code expires), so both run on a thread
+		// of their own rather than stalling the control plane. The user
+		// code and verification URL show up in this network's JSON once
+		// the begin step has stored them
+		std::shared_ptr<DeviceFlowState> flowState(new DeviceFlowState());
+		_deviceFlow = flowState;
+		zeroidc::ZeroIDC *idc = _idc;
+		std::thread([idc, flowState]() {
+			if (zeroidc::zeroidc_begin_device_flow(idc)) {
+				char *ret = zeroidc::zeroidc_device_token_exchange(idc);
+				if (ret != nullptr) {
+					zeroidc::free_cstr(ret);
+				}
+			}
+			if (flowState->finished.exchange(true)) {
+				// the network was torn down while we were polling, so the
+				// idc instance is ours to free
+				zeroidc::zeroidc_delete(idc);
+			}
+		}).detach();
+		return true;
+#else
+		return false;
+#endif
+	}
+
+	std::string deviceUserCode() {
+#if ZT_SSO_ENABLED
+		if (_idc == nullptr) {
+			return "";
+		}
+		char *code = zeroidc::zeroidc_get_device_user_code(_idc);
+		std::string ret(code);
+		zeroidc::free_cstr(code);
+		return ret;
+#else
+		return "";
+#endif
+	}
+
+	std::string deviceVerificationURL() {
+#if ZT_SSO_ENABLED
+		if (_idc == nullptr) {
+			return "";
+		}
+		char *url = zeroidc::zeroidc_get_device_verification_url(_idc);
+		std::string ret(url);
+		zeroidc::free_cstr(url);
+		return ret;
+#else
+		return "";
+#endif
+	}
+
 private:
 	unsigned int _webPort;
 	std::shared_ptr<EthernetTap> _tap;
@@ -476,6 +566,7 @@ private:
 	OneService::NetworkSettings _settings;
 #if ZT_SSO_ENABLED
 	zeroidc::ZeroIDC *_idc;
+	std::shared_ptr<DeviceFlowState> _deviceFlow;
 #endif
 };
 
@@ -593,6 +684,11 @@ static void _networkToJson(nlohmann::json &nj,NetworkState &ns)
 		nj["authenticationURL"] = authURL;
 		nj["authenticationExpiryTime"] = (ns.getExpiryTime()*1000);
 		nj["ssoEnabled"] = ns.config().ssoEnabled;
+		std::string deviceUserCode = ns.deviceUserCode();
+		if (!deviceUserCode.empty()) {
+			nj["ssoDeviceUserCode"] = deviceUserCode;
+			nj["ssoDeviceVerificationURL"] = ns.deviceVerificationURL();
+		}
 	}
 }
 
@@ -2076,6 +2172,13 @@ public:
 					if (allowDNS.is_boolean()) {
 						ns.setAllowDNS((bool)allowDNS);
 					}
+					// headless members can request the SSO device code flow
+					// here, then poll this network's JSON for the user code
+					// and verification URL to show the operator
+					json& beginSsoDeviceAuth = j["beginSsoDeviceAuth"];
+					if (beginSsoDeviceAuth.is_boolean() && (bool)beginSsoDeviceAuth) {
+						ns.startDeviceAuthFlow();
+					}
 				} catch (...) {
 					// discard invalid JSON
 				}